@@ -0,0 +1,268 @@
+// +---------------+
+// | Myers' O(ND) |
+// +---------------+
+
+// A single step of the shortest edit script between two line sequences,
+// indexed into the original `a`/`b` slices.
+#[derive(Debug, Clone, Copy)]
+enum DiffOp {
+  Equal(usize, usize),
+  Delete(usize),
+  Insert(usize),
+}
+
+// Finds the shortest edit script turning `a` into `b`, following Myers'
+// 1986 "An O(ND) Difference Algorithm". `v[k]` holds the furthest-reaching
+// x on diagonal `k` for the current edit distance `d`; `trace` snapshots
+// `v` before each round so `backtrack` can walk the script back to front.
+fn shortest_edit_script(a: &[&str], b: &[&str]) -> Vec<DiffOp> {
+  let n = a.len() as i64;
+  let m = b.len() as i64;
+  let max = n + m;
+
+  if max == 0 {
+    return Vec::new();
+  }
+
+  let offset = max as usize;
+  let idx = |k: i64| (k + offset as i64) as usize;
+
+  let mut v = vec![0i64; 2 * max as usize + 1];
+  let mut trace: Vec<Vec<i64>> = Vec::new();
+
+  for d in 0..=max {
+    trace.push(v.clone());
+
+    let mut k = -d;
+    while k <= d {
+      let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+        v[idx(k + 1)]
+      } else {
+        v[idx(k - 1)] + 1
+      };
+
+      let mut y = x - k;
+
+      while x < n && y < m && a[x as usize] == b[y as usize] {
+        x += 1;
+        y += 1;
+      }
+
+      v[idx(k)] = x;
+
+      if x >= n && y >= m {
+        return backtrack(n, m, &trace, offset);
+      }
+
+      k += 2;
+    }
+  }
+
+  unreachable!("Myers diff failed to converge")
+}
+
+fn backtrack(n: i64, m: i64, trace: &[Vec<i64>], offset: usize) -> Vec<DiffOp> {
+  let idx = |k: i64| (k + offset as i64) as usize;
+
+  let mut x = n;
+  let mut y = m;
+  let mut ops = Vec::new();
+
+  for d in (0..trace.len() as i64).rev() {
+    let v = &trace[d as usize];
+    let k = x - y;
+
+    let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+      k + 1
+    } else {
+      k - 1
+    };
+
+    let prev_x = v[idx(prev_k)];
+    let prev_y = prev_x - prev_k;
+
+    while x > prev_x && y > prev_y {
+      ops.push(DiffOp::Equal((x - 1) as usize, (y - 1) as usize));
+      x -= 1;
+      y -= 1;
+    }
+
+    if d > 0 {
+      if x == prev_x {
+        ops.push(DiffOp::Insert(prev_y as usize));
+      } else {
+        ops.push(DiffOp::Delete(prev_x as usize));
+      }
+    }
+
+    x = prev_x;
+    y = prev_y;
+  }
+
+  ops.reverse();
+  ops
+}
+
+// +---------+
+// | Opcodes |
+// +---------+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tag {
+  Equal,
+  Delete,
+  Insert,
+}
+
+// A contiguous run of one kind of edit, as ranges into `a` (i1..i2) and
+// `b` (j1..j2) — the same shape as Python's difflib opcodes.
+#[derive(Debug, Clone, Copy)]
+struct Opcode {
+  tag: Tag,
+  i1: usize,
+  i2: usize,
+  j1: usize,
+  j2: usize,
+}
+
+fn to_opcodes(ops: &[DiffOp]) -> Vec<Opcode> {
+  let mut opcodes: Vec<Opcode> = Vec::new();
+
+  for op in ops {
+    // The current position in `a` and `b`, which an insert or delete uses
+    // as the empty range on the side it doesn't touch.
+    let (a_pos, b_pos) = opcodes.last().map(|last| (last.i2, last.j2)).unwrap_or((0, 0));
+
+    let (tag, i1, i2, j1, j2) = match *op {
+      DiffOp::Equal(a_i, b_i) => (Tag::Equal, a_i, a_i + 1, b_i, b_i + 1),
+      DiffOp::Delete(a_i) => (Tag::Delete, a_i, a_i + 1, b_pos, b_pos),
+      DiffOp::Insert(b_i) => (Tag::Insert, a_pos, a_pos, b_i, b_i + 1),
+    };
+
+    if let Some(last) = opcodes.last_mut() {
+      let mergeable = match tag {
+        Tag::Equal => last.tag == Tag::Equal && last.i2 == i1 && last.j2 == j1,
+        Tag::Delete => last.tag == Tag::Delete && last.i2 == i1,
+        Tag::Insert => last.tag == Tag::Insert && last.j2 == j1,
+      };
+
+      if mergeable {
+        last.i2 = i2.max(last.i2);
+        last.j2 = j2.max(last.j2);
+        continue;
+      }
+    }
+
+    opcodes.push(Opcode { tag, i1, i2, j1, j2 });
+  }
+
+  opcodes
+}
+
+// Groups opcodes into hunks, trimming `equal` runs down to `context` lines
+// of padding and splitting into separate hunks wherever two changes are
+// more than `2 * context` lines apart, mirroring difflib's
+// `get_grouped_opcodes`.
+fn group_opcodes(mut opcodes: Vec<Opcode>, context: usize) -> Vec<Vec<Opcode>> {
+  if opcodes.is_empty() {
+    return Vec::new();
+  }
+
+  if let Some(first) = opcodes.first_mut() {
+    if first.tag == Tag::Equal {
+      first.i1 = first.i1.max(first.i2.saturating_sub(context));
+      first.j1 = first.j1.max(first.j2.saturating_sub(context));
+    }
+  }
+
+  if let Some(last) = opcodes.last_mut() {
+    if last.tag == Tag::Equal {
+      last.i2 = last.i2.min(last.i1 + context);
+      last.j2 = last.j2.min(last.j1 + context);
+    }
+  }
+
+  let span = context * 2;
+  let mut groups = Vec::new();
+  let mut group = Vec::new();
+
+  for opcode in opcodes {
+    let Opcode { tag, mut i1, i2, mut j1, j2 } = opcode;
+
+    if tag == Tag::Equal && i2 - i1 > span {
+      group.push(Opcode { tag, i1, i2: i1 + context, j1, j2: j1 + context });
+      groups.push(group);
+      group = Vec::new();
+      i1 = i1.max(i2.saturating_sub(context));
+      j1 = j1.max(j2.saturating_sub(context));
+    }
+
+    group.push(Opcode { tag, i1, i2, j1, j2 });
+  }
+
+  if !(group.len() == 1 && group[0].tag == Tag::Equal) {
+    groups.push(group);
+  }
+
+  groups
+}
+
+// +--------------+
+// | Unified diff |
+// +--------------+
+
+const CONTEXT: usize = 3;
+
+// Renders a unified diff between `a` and `b`, labelling the two sides with
+// `a_label`/`b_label`. Returns an empty string when the texts are identical.
+pub fn unified_diff(a: &str, b: &str, a_label: &str, b_label: &str) -> String {
+  let a_lines: Vec<&str> = a.lines().collect();
+  let b_lines: Vec<&str> = b.lines().collect();
+
+  let ops = shortest_edit_script(&a_lines, &b_lines);
+  let opcodes = to_opcodes(&ops);
+  let groups = group_opcodes(opcodes, CONTEXT);
+
+  if groups.is_empty() {
+    return String::new();
+  }
+
+  let mut out = format!("--- {}\n+++ {}\n", a_label, b_label);
+
+  for group in groups {
+    let i1 = group.first().unwrap().i1;
+    let i2 = group.last().unwrap().i2;
+    let j1 = group.first().unwrap().j1;
+    let j2 = group.last().unwrap().j2;
+
+    // A zero-length range reports its raw start rather than start+1 (the
+    // convention unified diff/patch use for an entirely empty side),
+    // mirroring difflib's `_format_range_unified`.
+    let a_start = if i2 == i1 { i1 } else { i1 + 1 };
+    let b_start = if j2 == j1 { j1 } else { j1 + 1 };
+
+    out.push_str(&format!("@@ -{},{} +{},{} @@\n", a_start, i2 - i1, b_start, j2 - j1));
+
+    for opcode in group {
+      match opcode.tag {
+        Tag::Equal => {
+          for i in opcode.i1..opcode.i2 {
+            out.push_str(&format!(" {}\n", a_lines[i]));
+          }
+        },
+        Tag::Delete => {
+          for i in opcode.i1..opcode.i2 {
+            out.push_str(&format!("-{}\n", a_lines[i]));
+          }
+        },
+        Tag::Insert => {
+          for j in opcode.j1..opcode.j2 {
+            out.push_str(&format!("+{}\n", b_lines[j]));
+          }
+        },
+      }
+    }
+  }
+
+  out
+}
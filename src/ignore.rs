@@ -0,0 +1,161 @@
+use anyhow::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// +--------------+
+// | Glob matching |
+// +--------------+
+
+// Matches a single path segment against a pattern containing `*`/`?`
+// wildcards, neither of which crosses a `/` boundary — segments are
+// already split apart by the caller.
+fn segment_match(pattern: &str, text: &str) -> bool {
+  fn helper(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+      None => text.is_empty(),
+      Some(b'*') => (0..=text.len()).any(|i| helper(&pattern[1..], &text[i..])),
+      Some(b'?') => !text.is_empty() && helper(&pattern[1..], &text[1..]),
+      Some(&c) => !text.is_empty() && text[0] == c && helper(&pattern[1..], &text[1..]),
+    }
+  }
+
+  helper(pattern.as_bytes(), text.as_bytes())
+}
+
+// Matches a `/`-separated pattern against a `/`-separated path, where a
+// `**` segment matches zero or more path segments and every other segment
+// is matched with `segment_match`.
+fn path_glob_match(pattern: &str, text: &str) -> bool {
+  fn helper(pattern: &[&str], text: &[&str]) -> bool {
+    match pattern.first() {
+      None => text.is_empty(),
+      Some(&"**") => {
+        if pattern.len() == 1 {
+          return true;
+        }
+        (0..=text.len()).any(|i| helper(&pattern[1..], &text[i..]))
+      },
+      Some(&segment) => {
+        !text.is_empty() && segment_match(segment, text[0]) && helper(&pattern[1..], &text[1..])
+      },
+    }
+  }
+
+  let pattern_parts: Vec<&str> = pattern.split('/').collect();
+  let text_parts: Vec<&str> = text.split('/').collect();
+  helper(&pattern_parts, &text_parts)
+}
+
+// +-------+
+// | Rule |
+// +-------+
+
+// One line of a `.gitignore`, scoped to the directory it was read from.
+struct Rule {
+  base: PathBuf,
+  pattern: String,
+  negate: bool,
+  anchored: bool,
+  dir_only: bool,
+}
+
+impl Rule {
+  fn parse(base: &Path, line: &str) -> Rule {
+    let negate = line.starts_with('!');
+    let line = if negate { &line[1..] } else { line };
+
+    let dir_only = line.ends_with('/');
+    let line = if dir_only { &line[..line.len() - 1] } else { line };
+
+    let leading_slash = line.starts_with('/');
+    let pattern = if leading_slash { &line[1..] } else { line };
+
+    // A pattern with a slash anywhere but the end is anchored to `base`;
+    // a pattern with no slash at all may match at any depth under `base`.
+    let anchored = leading_slash || pattern.contains('/');
+
+    Rule {
+      base: base.to_owned(),
+      pattern: pattern.to_owned(),
+      negate,
+      anchored,
+      dir_only,
+    }
+  }
+
+  fn matches(&self, path: &Path, is_dir: bool) -> bool {
+    if self.dir_only && !is_dir {
+      return false;
+    }
+
+    let relative = match path.strip_prefix(&self.base) {
+      Ok(relative) => relative,
+      Err(_) => return false,
+    };
+
+    if relative.as_os_str().is_empty() {
+      return false;
+    }
+
+    if self.anchored {
+      path_glob_match(&self.pattern, &relative.to_string_lossy())
+    } else {
+      let name = relative.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+      segment_match(&self.pattern, &name)
+    }
+  }
+}
+
+// +--------------+
+// | IgnoreRules |
+// +--------------+
+
+// All `.gitignore` rules found under a repository root, in root-to-leaf
+// order so that a nested `.gitignore` can override a parent's pattern
+// (the last matching rule wins, as in real Git).
+pub struct IgnoreRules {
+  rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+  pub fn load(root: &Path) -> Result<IgnoreRules> {
+    let mut gitignore_paths: Vec<PathBuf> = WalkDir::new(root)
+      .into_iter()
+      .filter_entry(|entry| entry.file_name() != ".git")
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| entry.file_name() == ".gitignore")
+      .map(|entry| entry.path().to_path_buf())
+      .collect();
+
+    gitignore_paths.sort_by_key(|path| path.components().count());
+
+    let mut rules = Vec::new();
+
+    for path in gitignore_paths {
+      let base = path.parent().unwrap_or(root).strip_prefix(root)?.to_path_buf();
+      let contents = fs::read_to_string(&path)?;
+
+      rules.extend(
+        contents.lines()
+          .map(|line| line.trim_end())
+          .filter(|line| !line.is_empty() && !line.starts_with('#'))
+          .map(|line| Rule::parse(&base, line))
+      );
+    }
+
+    Ok(IgnoreRules { rules })
+  }
+
+  pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+
+    for rule in &self.rules {
+      if rule.matches(path, is_dir) {
+        ignored = !rule.negate;
+      }
+    }
+
+    ignored
+  }
+}
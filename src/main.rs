@@ -1,23 +1,52 @@
 use anyhow::*;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path;
 use walkdir::WalkDir;
 
+mod diff;
+mod ignore;
 mod object_database;
+mod packfile;
+mod refs;
+use ignore::IgnoreRules;
 use object_database::*;
+use packfile::PackfileBuilder;
+use refs::Refs;
 
 fn get_cwd() -> path::PathBuf {
   path::Path::new("test/repo").to_path_buf()
 }
 
-fn do_init() -> Result<()> {
-  let dirs = vec![".git", ".git/objects", ".git/refs"];
+// The object format a repository was initialized with lives in a plain
+// text file alongside HEAD, mirroring how HEAD itself is a plain text ref.
+fn object_format_path() -> path::PathBuf {
+  get_cwd().join(".git/object-format")
+}
+
+fn read_object_format() -> Result<HashAlgorithm> {
+  match fs::read_to_string(object_format_path()) {
+    Ok(contents) => HashAlgorithm::from_str(contents.trim()),
+    Err(_) => Ok(HashAlgorithm::default()),
+  }
+}
+
+fn open_object_database() -> Result<ObjectDatabase> {
+  Ok(ObjectDatabase::with_hash_algorithm(get_cwd().join(".git/objects"), read_object_format()?))
+}
+
+fn do_init(object_format: &str) -> Result<()> {
+  let hash_algorithm = HashAlgorithm::from_str(object_format)?;
+  let dirs = vec![".git", ".git/objects", ".git/refs", ".git/refs/heads"];
 
   for dir in dirs {
     fs::create_dir_all(get_cwd().join(dir))?;
   }
 
+  Refs::new(get_cwd().join(".git")).set_head("master")?;
+  fs::write(object_format_path(), hash_algorithm.as_str())?;
+
   println!("Repository initialized!");
   Ok(())
 }
@@ -29,10 +58,16 @@ fn is_not_git_entry(entry: &walkdir::DirEntry) -> bool {
 fn do_commit() -> Result<()> {
   let walker = WalkDir::new(get_cwd()).into_iter();
 
-  let object_database = ObjectDatabase::new(get_cwd().join(".git/objects"));
+  let object_database = open_object_database()?;
+  let ignore_rules = IgnoreRules::load(&get_cwd())?;
+
+  let is_not_ignored = |entry: &walkdir::DirEntry| {
+    let relative = entry.path().strip_prefix(get_cwd()).unwrap_or(entry.path());
+    !ignore_rules.is_ignored(relative, entry.file_type().is_dir())
+  };
 
   let paths: Vec<_> = walker
-    .filter_entry(is_not_git_entry)
+    .filter_entry(|entry| is_not_git_entry(entry) && is_not_ignored(entry))
     .filter_map(|entry| {
       let entry = entry.unwrap();
       let is_not_dir = !entry.file_type().is_dir();
@@ -40,25 +75,22 @@ fn do_commit() -> Result<()> {
     })
     .collect();
 
-  let blobs: Vec<_> = paths
+  let blob_paths = paths
     .iter()
     .map(|path| {
       let contents = std::fs::read(path).unwrap();
-      Blob::new(&contents[..])
+      let object_id = object_database.write_object(&Blob::new(&contents[..]))?;
+      let path = path.strip_prefix(get_cwd()).unwrap().to_path_buf();
+      Ok((path, object_id))
     })
-    .collect();
-
-  let entries = paths.iter().zip(blobs.iter()).map(|(path, blob)| {
-    let path = path.strip_prefix(get_cwd()).unwrap().to_path_buf();
-    Entry::new(&path, blob.object_id())
-  }).collect::<Vec<_>>();
+    .collect::<Result<Vec<_>>>()?;
 
-  for blob in blobs {
-    object_database.write_object(&blob)?;
-  }
+  let tree = Tree::build(blob_paths, &object_database)?;
+  let tree_object_id = object_database.write_object(&tree)?;
 
-  let tree = Tree::new(entries);
-  object_database.write_object(&tree)?;
+  let refs = Refs::new(get_cwd().join(".git"));
+  let branch = refs.current_branch()?;
+  let parents = refs.head_commit()?.into_iter().collect::<Vec<_>>();
 
   let commit = Commit {
     author: Contributor::new("Martin Söderman", "kngrektor@gmail.com"),
@@ -66,16 +98,163 @@ fn do_commit() -> Result<()> {
     committer: Contributor::new("Martin Söderman", "kngrektor@gmail.com"),
     committed_at: chrono::offset::Utc::now(),
     message: "Väldigt coolt meddelanden!".to_owned(),
-    tree_object_id: tree.object_id()
+    tree_object_id,
+    parents: parents.clone()
+  };
+  let commit_object_id = object_database.write_object(&commit)?;
+
+  refs.update_branch(&branch, commit_object_id.clone())?;
+
+  let marker = if parents.is_empty() { format!("{} (root-commit)", branch) } else { branch };
+  println!("[{} {}] {}", marker, commit_object_id.as_hex(), commit.message);
+  Ok(())
+}
+
+fn do_cat_file(object_type: &str, oid: &str) -> Result<()> {
+  let object_database = open_object_database()?;
+  let object_id = ObjectId::from_hex(oid)?;
+  let bytes = object_database.read_object(&object_id)?;
+
+  match object_type {
+    "blob" => {
+      let blob = Blob::deserialize(bytes)?;
+      print!("{}", String::from_utf8_lossy(&blob.contents));
+    },
+    "tree" => {
+      let tree = Tree::deserialize_with_id_len(bytes, object_database.hash_algorithm().id_len())?;
+      for entry in &tree.entries {
+        let kind = if entry.mode == 40000 { "tree" } else { "blob" };
+        println!("{:06} {} {}\t{}", entry.mode, kind, entry.object_id, entry.path.display());
+      }
+    },
+    "commit" => {
+      let commit = Commit::deserialize(bytes)?;
+      println!("tree {}", commit.tree_object_id);
+      for parent in &commit.parents {
+        println!("parent {}", parent);
+      }
+      println!("author {} {}", commit.author, commit.authored_at.format("%s %z"));
+      println!("committer {} {}", commit.committer, commit.committed_at.format("%s %z"));
+      println!();
+      println!("{}", commit.message);
+    },
+    _ => println!("Error: unknown object type {:?}!", object_type),
+  }
+
+  Ok(())
+}
+
+fn do_branch(name: &str) -> Result<()> {
+  Refs::new(get_cwd().join(".git")).create_branch(name)?;
+  println!("Created branch {:?}", name);
+  Ok(())
+}
+
+fn do_log() -> Result<()> {
+  let refs = Refs::new(get_cwd().join(".git"));
+  let object_database = open_object_database()?;
+
+  let mut current = refs.head_commit()?;
+
+  while let Some(object_id) = current {
+    let commit = Commit::deserialize(object_database.read_object(&object_id)?)?;
+
+    println!("commit {}", object_id);
+    println!("Author: {}", commit.author);
+    println!();
+    println!("    {}", commit.message_summary());
+    println!();
+
+    current = commit.parents.first().cloned();
+  }
+
+  Ok(())
+}
+
+fn collect_blobs(
+  object_database: &ObjectDatabase,
+  tree: &Tree,
+  prefix: &path::Path,
+  blobs: &mut BTreeMap<path::PathBuf, ObjectId>,
+) -> Result<()> {
+  for entry in &tree.entries {
+    let entry_path = prefix.join(&entry.path);
+
+    if entry.mode == 40000 {
+      let id_len = object_database.hash_algorithm().id_len();
+      let subtree = Tree::deserialize_with_id_len(object_database.read_object(&entry.object_id)?, id_len)?;
+      collect_blobs(object_database, &subtree, &entry_path, blobs)?;
+    } else {
+      blobs.insert(entry_path, entry.object_id.clone());
+    }
+  }
+
+  Ok(())
+}
+
+fn do_diff() -> Result<()> {
+  let refs = Refs::new(get_cwd().join(".git"));
+  let object_database = open_object_database()?;
+
+  let head_commit_id = match refs.head_commit()? {
+    Some(object_id) => object_id,
+    None => {
+      println!("No commits yet");
+      return Ok(());
+    },
   };
-  object_database.write_object(&commit)?;
 
-  std::fs::write(get_cwd().join(".git/HEAD"), commit.object_id().as_hex())?;
+  let commit = Commit::deserialize(object_database.read_object(&head_commit_id)?)?;
+  let root_tree = Tree::deserialize_with_id_len(
+    object_database.read_object(&commit.tree_object_id)?,
+    object_database.hash_algorithm().id_len(),
+  )?;
+
+  let mut blobs = BTreeMap::new();
+  collect_blobs(&object_database, &root_tree, path::Path::new(""), &mut blobs)?;
+
+  for (path, object_id) in &blobs {
+    let old_blob = Blob::deserialize(object_database.read_object(object_id)?)?;
+    let old_contents = String::from_utf8_lossy(&old_blob.contents).into_owned();
+    let new_contents = fs::read_to_string(get_cwd().join(path)).unwrap_or_default();
+
+    let a_label = format!("a/{}", path.display());
+    let b_label = format!("b/{}", path.display());
+
+    print!("{}", diff::unified_diff(&old_contents, &new_contents, &a_label, &b_label));
+  }
 
-  println!("[(root-commit) {}] {}", commit.object_id().as_hex(), commit.message);
   Ok(())
 }
 
+fn do_pack_objects(oids: &[String]) -> Result<()> {
+  let object_database = open_object_database()?;
+
+  let object_ids = oids.iter()
+    .map(|oid| ObjectId::from_hex(oid))
+    .collect::<Result<Vec<_>>>()?;
+
+  let pack = PackfileBuilder::new(&object_database).build(&object_ids)?;
+
+  let pack_hex = digest_hex(&pack);
+  let pack_dir = get_cwd().join(".git/objects/pack");
+  fs::create_dir_all(&pack_dir)?;
+
+  let pack_path = pack_dir.join(format!("pack-{}.pack", pack_hex));
+  fs::write(&pack_path, &pack)?;
+
+  println!("{}", pack_path.display());
+  Ok(())
+}
+
+fn digest_hex(bytes: &[u8]) -> String {
+  ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, bytes)
+    .as_ref()
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
 fn do_help() -> Result<()> {
   println!("Here's the help!");
   Ok(())
@@ -87,8 +266,30 @@ fn main() -> Result<()> {
   let command = args.next().unwrap_or("help".to_owned());
 
   match command.as_str() {
-    "init" => do_init()?,
+    "init" => {
+      let mut object_format = "sha1".to_owned();
+
+      for arg in args.by_ref() {
+        if let Some(value) = arg.strip_prefix("--object-format=") {
+          object_format = value.to_owned();
+        }
+      }
+
+      do_init(&object_format)?
+    },
     "commit" => do_commit()?,
+    "cat-file" => {
+      let object_type = args.next().ok_or_else(|| anyhow!("cat-file requires a type"))?;
+      let oid = args.next().ok_or_else(|| anyhow!("cat-file requires an object id"))?;
+      do_cat_file(&object_type, &oid)?
+    },
+    "branch" => {
+      let name = args.next().ok_or_else(|| anyhow!("branch requires a name"))?;
+      do_branch(&name)?
+    },
+    "log" => do_log()?,
+    "diff" => do_diff()?,
+    "pack-objects" => do_pack_objects(&args.collect::<Vec<_>>())?,
     "help" => do_help()?,
     _ => println!("Error: unknown command {:?}!", command),
   }
@@ -1,23 +1,96 @@
 use anyhow::*;
-use path::Display;
-use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
-use std::convert::{Into, TryInto};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
+use std::convert::Into;
+use std::io::prelude::*;
 use std::path;
 use chrono::prelude::*;
 
+// +----------------+
+// | HashAlgorithm  |
+// +----------------+
+
+// The hash used to name objects in a repository. SHA-1 is Git's legacy
+// default; SHA-256 is the opt-in "object format" newer repositories can use.
+#[derive(Clone, Copy, PartialEq)]
+pub enum HashAlgorithm {
+  Sha1,
+  Sha256,
+}
+
+impl HashAlgorithm {
+  pub fn from_str(name: &str) -> Result<HashAlgorithm> {
+    match name {
+      "sha1" => Ok(HashAlgorithm::Sha1),
+      "sha256" => Ok(HashAlgorithm::Sha256),
+      _ => Err(anyhow!("unknown object format {:?}, expected \"sha1\" or \"sha256\"", name)),
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      HashAlgorithm::Sha1 => "sha1",
+      HashAlgorithm::Sha256 => "sha256",
+    }
+  }
+
+  fn ring_algorithm(&self) -> &'static ring::digest::Algorithm {
+    match self {
+      HashAlgorithm::Sha1 => &SHA1_FOR_LEGACY_USE_ONLY,
+      HashAlgorithm::Sha256 => &SHA256,
+    }
+  }
+
+  // The width in bytes of an object id produced by this algorithm: 20 for
+  // SHA-1, 32 for SHA-256.
+  pub fn id_len(&self) -> usize {
+    match self {
+      HashAlgorithm::Sha1 => 20,
+      HashAlgorithm::Sha256 => 32,
+    }
+  }
+}
+
+impl Default for HashAlgorithm {
+  fn default() -> Self {
+    HashAlgorithm::Sha1
+  }
+}
+
 // +--------+
 // | Object |
 // +--------+
-#[derive(Clone, Copy)]
-pub struct ObjectId([u8; 20]);
+
+// A variable-width object digest: 20 bytes under SHA-1, 32 under SHA-256.
+// The width isn't recorded on the id itself, since every id within one
+// repository is produced by the same `ObjectDatabase::hash_algorithm`.
+#[derive(Clone, PartialEq)]
+pub struct ObjectId {
+  bytes: Vec<u8>,
+}
 
 impl ObjectId {
-  pub fn as_bytes(&self) -> [u8; 20] {
-    self.0
+  pub fn from_hex(hex: &str) -> Result<ObjectId> {
+    if hex.len() % 2 != 0 {
+      return Err(anyhow!("invalid object id: odd number of hex characters ({})", hex.len()));
+    }
+
+    let bytes = (0..hex.len())
+      .step_by(2)
+      .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+      .collect::<Result<Vec<u8>>>()?;
+
+    Ok(ObjectId { bytes })
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.bytes
   }
 
   pub fn as_hex(&self) -> String {
-    self.0.iter()
+    self.bytes.iter()
       .map(|b| format!("{:02x}", b))
       .collect::<String>()
   }
@@ -32,16 +105,6 @@ impl std::fmt::Display for ObjectId {
 pub trait Object: Sized {
   fn serialize(&self) -> Vec<u8>;
   fn deserialize(bytes: Vec<u8>) -> Result<Self>;
-
-  fn object_id(&self) -> ObjectId {
-    let serialized = self.serialize();
-    let object_id = digest(&SHA1_FOR_LEGACY_USE_ONLY, &serialized[..])
-      .as_ref()
-      .try_into()
-      .unwrap();
-
-    ObjectId(object_id)
-  }
 }
 
 // +----------------+
@@ -50,25 +113,68 @@ pub trait Object: Sized {
 
 pub struct ObjectDatabase {
   objects_path: path::PathBuf,
+  hash_algorithm: HashAlgorithm,
 }
 
 impl ObjectDatabase {
   pub fn new<T: Into<path::PathBuf>>(objects_path: T) -> Self {
+    Self::with_hash_algorithm(objects_path, HashAlgorithm::default())
+  }
+
+  pub fn with_hash_algorithm<T: Into<path::PathBuf>>(objects_path: T, hash_algorithm: HashAlgorithm) -> Self {
     ObjectDatabase {
       objects_path: objects_path.into(),
+      hash_algorithm,
     }
   }
 
-  pub fn write_object<T: Object>(&self, object: &T) -> Result<()> {
-    let object_id = object.object_id().as_hex();
-    let (dir_name, file_name) = object_id.split_at(2);
+  pub fn object_id<T: Object>(&self, object: &T) -> ObjectId {
+    let bytes = digest(self.hash_algorithm.ring_algorithm(), &object.serialize()[..])
+      .as_ref()
+      .to_vec();
+
+    ObjectId { bytes }
+  }
+
+  pub fn write_object<T: Object>(&self, object: &T) -> Result<ObjectId> {
+    let object_id = self.object_id(object);
+    let hex = object_id.as_hex();
+    let (dir_name, file_name) = hex.split_at(2);
 
     let dir_name = self.objects_path.join(dir_name);
 
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&object.serialize()[..])?;
+    let compressed = encoder.finish()?;
+
     std::fs::create_dir_all(&dir_name)?;
-    std::fs::write(&dir_name.join(file_name), object.serialize())?;
+    std::fs::write(&dir_name.join(file_name), compressed)?;
+
+    Ok(object_id)
+  }
 
-    Ok(())
+  pub fn hash_algorithm(&self) -> HashAlgorithm {
+    self.hash_algorithm
+  }
+
+  pub fn read_object(&self, object_id: &ObjectId) -> Result<Vec<u8>> {
+    let hex = object_id.as_hex();
+    let (dir_name, file_name) = hex.split_at(2);
+
+    let path = self.objects_path.join(dir_name).join(file_name);
+    let compressed = std::fs::read(&path)?;
+
+    let mut decoder = ZlibDecoder::new(&compressed[..]);
+    let mut bytes = Vec::new();
+    decoder.read_to_end(&mut bytes)?;
+
+    let digest = digest(self.hash_algorithm.ring_algorithm(), &bytes[..]);
+
+    if digest.as_ref() != object_id.as_bytes() {
+      return Err(anyhow!("object {} is corrupt: hash mismatch", hex));
+    }
+
+    Ok(bytes)
   }
 }
 
@@ -115,14 +221,37 @@ pub struct Entry {
   pub object_id: ObjectId,
 }
 
+const BLOB_MODE: u64 = 100644;
+const TREE_MODE: u64 = 40000;
+
 impl Entry {
-  pub fn new(path: &path::PathBuf, object_id: ObjectId) -> Self {
+  pub fn new_blob(name: &path::Path, object_id: ObjectId) -> Self {
+    Entry {
+      mode: BLOB_MODE,
+      path: name.to_owned(),
+      object_id: object_id,
+    }
+  }
+
+  pub fn new_tree(name: &path::Path, object_id: ObjectId) -> Self {
     Entry {
-      mode: 100644,
-      path: path.to_owned(),
+      mode: TREE_MODE,
+      path: name.to_owned(),
       object_id: object_id,
     }
   }
+
+  // Git sorts tree entries as if directory names had a trailing '/', so
+  // that e.g. "foo" (a directory) sorts after "foo.txt" rather than before.
+  fn sort_key(&self) -> String {
+    let name = self.path.to_string_lossy().into_owned();
+
+    if self.mode == TREE_MODE {
+      format!("{}/", name)
+    } else {
+      name
+    }
+  }
 }
 
 // +------+
@@ -130,13 +259,58 @@ impl Entry {
 // +------+
 
 pub struct Tree {
-  entries: Vec<Entry>,
+  pub entries: Vec<Entry>,
 }
 
 impl Tree {
-  pub fn new(entries: Vec<Entry>) -> Self {
+  pub fn new(mut entries: Vec<Entry>) -> Self {
+    entries.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
     Tree { entries }
   }
+
+  // Builds a tree (and any nested subtrees) from a flat list of relative
+  // blob paths, writing each subtree to the object database as it's built
+  // so the parent tree can reference it by ObjectId.
+  pub fn build<T: Into<path::PathBuf>>(
+    blobs: Vec<(T, ObjectId)>,
+    object_database: &ObjectDatabase,
+  ) -> Result<Tree> {
+    use std::collections::BTreeMap;
+    use std::ffi::OsString;
+
+    let mut groups: BTreeMap<OsString, Vec<(path::PathBuf, ObjectId)>> = BTreeMap::new();
+
+    for (path, object_id) in blobs {
+      let path = path.into();
+      let mut components = path.components();
+      let first = components
+        .next()
+        .ok_or_else(|| anyhow!("cannot build a tree entry from an empty path"))?;
+      let rest = components.as_path().to_path_buf();
+
+      groups
+        .entry(first.as_os_str().to_owned())
+        .or_insert_with(Vec::new)
+        .push((rest, object_id));
+    }
+
+    let mut entries = Vec::new();
+
+    for (name, children) in groups {
+      let name = path::Path::new(&name);
+
+      if children.len() == 1 && children[0].0.as_os_str().is_empty() {
+        let (_, object_id) = children.into_iter().next().unwrap();
+        entries.push(Entry::new_blob(name, object_id));
+      } else {
+        let subtree = Tree::build(children, object_database)?;
+        let object_id = object_database.write_object(&subtree)?;
+        entries.push(Entry::new_tree(name, object_id));
+      }
+    }
+
+    Ok(Tree::new(entries))
+  }
 }
 
 impl Object for Tree {
@@ -144,7 +318,7 @@ impl Object for Tree {
     let mut bytes = self.entries.iter()
       .map(|entry| {
         let mut bytes = format!("{} {}\0", entry.mode, entry.path.display()).into_bytes();
-        bytes.extend(&entry.object_id.as_bytes());
+        bytes.extend(entry.object_id.as_bytes());
         bytes
       })
       .flatten()
@@ -155,8 +329,57 @@ impl Object for Tree {
     contents
   }
 
+  // A tree doesn't record which hash algorithm produced its entries' ids
+  // (that's a repository-wide choice), so `Object::deserialize` can only
+  // assume the SHA-1 width; callers that know the repository's object
+  // format should call `Tree::deserialize_with_id_len` instead.
   fn deserialize(bytes: Vec<u8>) -> Result<Tree> {
-    std::unimplemented!()
+    Tree::deserialize_with_id_len(bytes, HashAlgorithm::default().id_len())
+  }
+}
+
+impl Tree {
+  // Like `Object::deserialize`, but takes the object id width explicitly
+  // so trees from a SHA-256 repository parse correctly too.
+  pub fn deserialize_with_id_len(bytes: Vec<u8>, id_len: usize) -> Result<Tree> {
+    let header_end = bytes.iter().position(|b| *b == 0u8)
+      .ok_or_else(|| anyhow!("invalid tree object: missing header terminator"))?;
+
+    let mut rest = &bytes[header_end + 1..];
+    let mut entries = Vec::new();
+
+    while !rest.is_empty() {
+      let name_end = rest.iter().position(|b| *b == 0u8)
+        .ok_or_else(|| anyhow!("invalid tree entry: missing name terminator"))?;
+
+      let header = std::str::from_utf8(&rest[..name_end])?;
+      let mut parts = header.splitn(2, ' ');
+
+      let mode: u64 = parts.next()
+        .ok_or_else(|| anyhow!("invalid tree entry: missing mode"))?
+        .parse()?;
+
+      let name = parts.next()
+        .ok_or_else(|| anyhow!("invalid tree entry: missing name"))?;
+
+      rest = &rest[name_end + 1..];
+
+      if rest.len() < id_len {
+        return Err(anyhow!("invalid tree entry: truncated object id"));
+      }
+
+      let (id_bytes, remainder) = rest.split_at(id_len);
+      let object_id = ObjectId { bytes: id_bytes.to_vec() };
+      rest = remainder;
+
+      entries.push(if mode == TREE_MODE {
+        Entry::new_tree(path::Path::new(name), object_id)
+      } else {
+        Entry::new_blob(path::Path::new(name), object_id)
+      });
+    }
+
+    Ok(Tree::new(entries))
   }
 }
 
@@ -184,6 +407,32 @@ impl std::fmt::Display for Contributor {
   }
 }
 
+// Parses a `<name> <email> <unix-ts> <tz>` line (as found after the
+// "author "/"committer " prefix in a commit object) into a Contributor
+// and the instant it names.
+fn parse_contributor(line: &str) -> Result<(Contributor, chrono::DateTime<Utc>)> {
+  let email_start = line.find('<')
+    .ok_or_else(|| anyhow!("invalid contributor line: missing '<'"))?;
+  let email_end = line.find('>')
+    .ok_or_else(|| anyhow!("invalid contributor line: missing '>'"))?;
+
+  let name = line[..email_start].trim().to_owned();
+  let email = line[email_start + 1..email_end].to_owned();
+
+  let mut rest = line[email_end + 1..].trim().split_whitespace();
+
+  let timestamp: i64 = rest.next()
+    .ok_or_else(|| anyhow!("invalid contributor line: missing timestamp"))?
+    .parse()?;
+
+  rest.next().ok_or_else(|| anyhow!("invalid contributor line: missing timezone"))?;
+
+  let naive = chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0)
+    .ok_or_else(|| anyhow!("invalid contributor line: timestamp out of range"))?;
+
+  Ok((Contributor::new(name, email), chrono::DateTime::from_utc(naive, Utc)))
+}
+
 // +--------+
 // | Commit |
 // +--------+
@@ -194,7 +443,8 @@ pub struct Commit {
   pub committer: Contributor,
   pub committed_at: chrono::DateTime<Utc>,
   pub message: String,
-  pub tree_object_id: ObjectId
+  pub tree_object_id: ObjectId,
+  pub parents: Vec<ObjectId>
 }
 
 impl Commit {
@@ -204,41 +454,94 @@ impl Commit {
     committer: Contributor,
     committed_at: chrono::DateTime<Utc>,
     message: String,
-    tree_object_id: ObjectId
+    tree_object_id: ObjectId,
+    parents: Vec<ObjectId>
   ) -> Commit {
     Commit {
       author, authored_at,
       committer, committed_at,
       message,
-      tree_object_id
+      tree_object_id,
+      parents
     }
   }
   pub fn message_summary(&self) -> &str {
-    let len = self.message.find('\n')
-      .unwrap_or(40)
+    let mut len = self.message.find('\n')
+      .unwrap_or(self.message.len())
       .min(40);
+
+    while len > 0 && !self.message.is_char_boundary(len) {
+      len -= 1;
+    }
+
     &self.message[..len]
   }
 }
 
 impl Object for Commit {
   fn serialize(&self) -> Vec<u8> {
-  let mut bytes = format!(r#"tree {}
-      author {} {}
-      commit {} {}
-      {}"#,
-      self.tree_object_id,
+    let mut header = format!("tree {}\n", self.tree_object_id);
+
+    for parent in &self.parents {
+      header.push_str(&format!("parent {}\n", parent));
+    }
+
+    header.push_str(&format!(
+      "author {} {}\ncommitter {} {}\n\n{}",
       self.author, self.authored_at.format("%s %z"),
       self.committer, self.committed_at.format("%s %z"),
       self.message
-    ).into_bytes();
-    
+    ));
+
+    let mut bytes = header.into_bytes();
     let mut contents = format!("commit {}\0", bytes.len()).into_bytes();
     contents.append(&mut bytes);
     contents
   }
 
   fn deserialize(bytes: Vec<u8>) -> Result<Commit> {
-    std::unimplemented!()
+    let header_end = bytes.iter().position(|b| *b == 0u8)
+      .ok_or_else(|| anyhow!("invalid commit object: missing header terminator"))?;
+
+    let body = std::str::from_utf8(&bytes[header_end + 1..])?;
+    let mut lines = body.lines();
+
+    let tree_line = lines.next()
+      .ok_or_else(|| anyhow!("invalid commit object: missing tree line"))?;
+    let tree_hex = tree_line.strip_prefix("tree ")
+      .ok_or_else(|| anyhow!("invalid commit object: expected tree line"))?;
+    let tree_object_id = ObjectId::from_hex(tree_hex)?;
+
+    let mut line = lines.next()
+      .ok_or_else(|| anyhow!("invalid commit object: missing author line"))?;
+
+    let mut parents = Vec::new();
+    while let Some(parent_hex) = line.strip_prefix("parent ") {
+      parents.push(ObjectId::from_hex(parent_hex)?);
+      line = lines.next()
+        .ok_or_else(|| anyhow!("invalid commit object: missing author line"))?;
+    }
+
+    let author_line = line.strip_prefix("author ")
+      .ok_or_else(|| anyhow!("invalid commit object: expected author line"))?;
+    let (author, authored_at) = parse_contributor(author_line)?;
+
+    let committer_line = lines.next()
+      .ok_or_else(|| anyhow!("invalid commit object: missing committer line"))?
+      .strip_prefix("committer ")
+      .ok_or_else(|| anyhow!("invalid commit object: expected committer line"))?;
+    let (committer, committed_at) = parse_contributor(committer_line)?;
+
+    lines.next(); // the blank line separating headers from the message
+
+    let message = lines.collect::<Vec<_>>().join("\n");
+
+    Ok(Commit {
+      author, authored_at,
+      committer, committed_at,
+      message,
+      tree_object_id,
+      parents
+    })
   }
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+use anyhow::*;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use std::io::prelude::*;
+
+use crate::object_database::{ObjectDatabase, ObjectId};
+
+// The four object types a Git pack entry's header can name when it's a
+// full (non-delta) object, as laid out in Documentation/gitformat-pack.txt.
+#[derive(Clone, Copy)]
+enum PackObjectType {
+  Commit,
+  Tree,
+  Blob,
+  Tag,
+}
+
+impl PackObjectType {
+  fn from_str(name: &str) -> Result<PackObjectType> {
+    match name {
+      "commit" => Ok(PackObjectType::Commit),
+      "tree" => Ok(PackObjectType::Tree),
+      "blob" => Ok(PackObjectType::Blob),
+      "tag" => Ok(PackObjectType::Tag),
+      _ => Err(anyhow!("cannot pack object of unknown type {:?}", name)),
+    }
+  }
+
+  fn as_u8(&self) -> u8 {
+    match self {
+      PackObjectType::Commit => 1,
+      PackObjectType::Tree => 2,
+      PackObjectType::Blob => 3,
+      PackObjectType::Tag => 4,
+    }
+  }
+}
+
+// Encodes a pack entry's type/size header: the first byte packs a
+// continuation bit, the 3-bit type, and the low 4 bits of the size;
+// remaining size bits follow in 7-bit little-endian groups, each with its
+// own continuation bit.
+fn encode_entry_header(object_type: PackObjectType, size: u64) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  let mut size = size;
+
+  let mut byte = (object_type.as_u8() << 4) | (size & 0x0f) as u8;
+  size >>= 4;
+
+  if size > 0 {
+    byte |= 0x80;
+  }
+  bytes.push(byte);
+
+  while size > 0 {
+    let mut next = (size & 0x7f) as u8;
+    size >>= 7;
+
+    if size > 0 {
+      next |= 0x80;
+    }
+    bytes.push(next);
+  }
+
+  bytes
+}
+
+// Builds a Git v2 packfile out of loose objects, writing each as a full
+// (non-delta) entry. This is the high-level half of the packfile support;
+// a lower-level delta-aware writer would sit alongside it later.
+pub struct PackfileBuilder<'a> {
+  object_database: &'a ObjectDatabase,
+}
+
+impl<'a> PackfileBuilder<'a> {
+  pub fn new(object_database: &'a ObjectDatabase) -> Self {
+    PackfileBuilder { object_database }
+  }
+
+  pub fn build(&self, object_ids: &[ObjectId]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    bytes.extend_from_slice(b"PACK");
+    bytes.extend_from_slice(&2u32.to_be_bytes());
+    bytes.extend_from_slice(&(object_ids.len() as u32).to_be_bytes());
+
+    for object_id in object_ids {
+      let (object_type, payload) = self.read_entry(object_id)?;
+
+      bytes.extend(encode_entry_header(object_type, payload.len() as u64));
+
+      let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+      encoder.write_all(&payload)?;
+      bytes.extend(encoder.finish()?);
+    }
+
+    let checksum = digest(&SHA1_FOR_LEGACY_USE_ONLY, &bytes);
+    bytes.extend_from_slice(checksum.as_ref());
+
+    Ok(bytes)
+  }
+
+  fn read_entry(&self, object_id: &ObjectId) -> Result<(PackObjectType, Vec<u8>)> {
+    let bytes = self.object_database.read_object(object_id)?;
+
+    let header_end = bytes.iter().position(|b| *b == 0u8)
+      .ok_or_else(|| anyhow!("invalid object {}: missing header terminator", object_id))?;
+
+    let header = std::str::from_utf8(&bytes[..header_end])?;
+    let type_name = header.split(' ').next()
+      .ok_or_else(|| anyhow!("invalid object {}: missing type", object_id))?;
+
+    Ok((PackObjectType::from_str(type_name)?, bytes[header_end + 1..].to_vec()))
+  }
+}
@@ -0,0 +1,71 @@
+use anyhow::*;
+use std::fs;
+use std::path;
+
+use crate::object_database::ObjectId;
+
+// +------+
+// | Refs |
+// +------+
+
+// Resolves `.git/HEAD` as a symbolic ref (`ref: refs/heads/<branch>`) and
+// reads/writes the branch tips it can point at under `.git/refs/heads/`.
+pub struct Refs {
+  git_dir: path::PathBuf,
+}
+
+impl Refs {
+  pub fn new<T: Into<path::PathBuf>>(git_dir: T) -> Self {
+    Refs { git_dir: git_dir.into() }
+  }
+
+  fn head_path(&self) -> path::PathBuf {
+    self.git_dir.join("HEAD")
+  }
+
+  fn heads_dir(&self) -> path::PathBuf {
+    self.git_dir.join("refs/heads")
+  }
+
+  pub fn current_branch(&self) -> Result<String> {
+    let head = fs::read_to_string(self.head_path())?;
+    let head = head.trim();
+
+    head.strip_prefix("ref: refs/heads/")
+      .map(|branch| branch.to_owned())
+      .ok_or_else(|| anyhow!("HEAD is not a symbolic ref to a branch: {:?}", head))
+  }
+
+  pub fn set_head(&self, branch: &str) -> Result<()> {
+    fs::write(self.head_path(), format!("ref: refs/heads/{}\n", branch))?;
+    Ok(())
+  }
+
+  pub fn branch_tip(&self, branch: &str) -> Result<Option<ObjectId>> {
+    let path = self.heads_dir().join(branch);
+
+    if !path.exists() {
+      return Ok(None);
+    }
+
+    let hex = fs::read_to_string(path)?;
+    Ok(Some(ObjectId::from_hex(hex.trim())?))
+  }
+
+  pub fn head_commit(&self) -> Result<Option<ObjectId>> {
+    self.branch_tip(&self.current_branch()?)
+  }
+
+  pub fn update_branch(&self, branch: &str, object_id: ObjectId) -> Result<()> {
+    fs::create_dir_all(self.heads_dir())?;
+    fs::write(self.heads_dir().join(branch), object_id.as_hex())?;
+    Ok(())
+  }
+
+  pub fn create_branch(&self, name: &str) -> Result<()> {
+    let tip = self.head_commit()?
+      .ok_or_else(|| anyhow!("cannot create branch {:?}: HEAD has no commits yet", name))?;
+
+    self.update_branch(name, tip)
+  }
+}